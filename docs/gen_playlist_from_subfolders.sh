@@ -17,28 +17,48 @@
 
 # ------------------------------------------------------------------------------
 
+# usage: gen_playlist_from_subfolders.sh [-n] /path/to/mp4s/
+# -n: dry run, print playlist and fill analysis, without writing the file
+# ReplayGain track gain tags are written as "gain" to the clips
+
+dryRun=false
+
+if [[ "$1" == "-n" ]]; then
+	dryRun=true
+	shift
+fi
+
 src=$1
 
 listDate=$(date +%Y-%m-%d)
 
 trunk="/playlists/$(date +%Y)/$(date +%m)/"
 playlist="$listDate.json"
+target="$trunk/$playlist"
 
-[[ -d "$trunk" ]] || mkdir -p "$trunk"
+if $dryRun; then
+	target="/dev/stdout"
+else
+	[[ -d "$trunk" ]] || mkdir -p "$trunk"
+fi
 
 c="0"
+total="0"
 count=$( find "$src" -name "*.mp4" | wc -l )
 
 # build Head for playlist
 printf  '{
 	"channel": "Test 1",
 	"date": "%s",
-	"program": [{\n' $listDate > "$trunk/$playlist"
+	"program": [{\n' $listDate > "$target"
 
 # read playlist
 while read -r line; do
 	clipPath=$(echo "$line" | sed 's/&/&amp;/g')
-	clipDuration=$( ffprobe -v error -show_format  "$line" | awk -F= '/duration/{ print $2 }' )
+	probe=$( ffprobe -v error -show_format  "$line" )
+	clipDuration=$( echo "$probe" | awk -F= '/^duration/{ print $2 }' )
+	clipGain=$( echo "$probe" | awk -F= 'tolower($1) ~ /replaygain_track_gain/{ sub(/ *dB/, "", $2); sub(/^[+]/, "", $2); print $2; exit }' )
+	total=$( awk -v t="$total" -v d="$clipDuration" 'BEGIN { print t + d }' )
 
 	c=$((c + 1))
 
@@ -48,8 +68,26 @@ while read -r line; do
 		last="}]"
 	fi
 
-	printf '\t\t"in": 0,\n\t\t"out": %s,\n\t\t"duration": %s,\n\t\t"source": "%s"\n\t%s\n' "$clipDuration" "$clipDuration" "$clipPath" "$last" >> "$trunk/$playlist"
+	if [[ -n "$clipGain" ]]; then
+		gain=$(printf ',\n\t\t"gain": %s' "$clipGain")
+	else
+		gain=""
+	fi
+
+	printf '\t\t"in": 0,\n\t\t"out": %s,\n\t\t"duration": %s,\n\t\t"source": "%s"%s\n\t%s\n' "$clipDuration" "$clipDuration" "$clipPath" "$gain" "$last" >> "$target"
 
 done < <( find "$src" -name "*.mp4" | sort -R)
 
-printf "}\n" >> "$trunk/$playlist"
+printf "}\n" >> "$target"
+
+if $dryRun; then
+	# fill analysis, compared to 24 hours
+	awk -v t="$total" -v c="$c" 'BEGIN {
+		printf "clips: %d, total length: %.2f seconds, ", c, t
+		if (t < 86400) {
+			printf "missing: %.2f seconds\n", 86400 - t
+		} else {
+			printf "over: %.2f seconds\n", t - 86400
+		}
+	}' >&2
+fi
@@ -19,14 +19,20 @@
 # ------------------------------------------------------------------------------
 
 import os
+import shlex
 from subprocess import PIPE, Popen
 from threading import Thread
 
+from ffplayout.control import start_control_server
 from ffplayout.folder import GetSourceFromFolder, MediaStore, MediaWatcher
 from ffplayout.playlist import GetSourceFromPlaylist
-from ffplayout.utils import (_ff, _log, _playlist, _playout, _pre_comp, _text,
-                             ffmpeg_stderr_reader, get_date, messenger,
-                             pre_audio_codec, stdin_args, terminate_processes)
+from ffplayout.utils import (_control, _current, _ff, _playlist, _playout,
+                             _pre_comp, _text, decoder_logger, encoder_logger,
+                             ff_log_level, ffmpeg_stderr_reader, get_date,
+                             get_time, messenger, output_target,
+                             post_process_watcher, pre_audio_codec, quarantine,
+                             stage_timer, stdin_args, terminate_processes,
+                             webhook)
 
 try:
     if os.name != 'posix':
@@ -77,20 +83,39 @@ def main():
                 ] + overlay, stderr=PIPE, stdin=PIPE, stdout=None)
         else:
             _ff.encoder = Popen([
-                'ffmpeg', '-v', _log.ff_level.lower(), '-hide_banner',
+                'ffmpeg', '-v', ff_log_level(encoder_logger), '-hide_banner',
                 '-nostats', '-re', '-thread_queue_size', '256',
-                '-i', 'pipe:0'] + overlay + [
+                '-i', 'pipe:0'] + output_target(overlay, [
                     '-metadata', 'service_name=' + _playout.name,
                     '-metadata', 'service_provider=' + _playout.provider,
                     '-metadata', 'year={}'.format(year)
-                ] + _playout.post_comp_param + [_playout.out_addr],
-                stdin=PIPE, stderr=PIPE)
+                ]),
+                stdin=PIPE, stderr=PIPE,
+                stdout=PIPE if _playout.post_process else None)
+
+            if _playout.post_process:
+                messenger.info('Pipe output to post process: "{}"'.format(
+                    _playout.post_process))
+                _ff.post_process = Popen(
+                    shlex.split(_playout.post_process),
+                    stdin=_ff.encoder.stdout, stderr=PIPE)
+                _ff.encoder.stdout.close()
+
+                post_thread = Thread(target=post_process_watcher,
+                                     args=(_ff.post_process,))
+                post_thread.daemon = True
+                post_thread.start()
 
         enc_err_thread = Thread(target=ffmpeg_stderr_reader,
                                 args=(_ff.encoder.stderr, False))
         enc_err_thread.daemon = True
         enc_err_thread.start()
 
+        if _control.enable:
+            start_control_server()
+
+        webhook('playout_start')
+
         if _playlist.mode and not stdin_args.folder:
             watcher = None
             get_source = GetSourceFromPlaylist()
@@ -103,16 +128,17 @@ def main():
         try:
             for src_cmd in get_source.next():
                 messenger.debug('src_cmd: "{}"'.format(src_cmd))
-                if src_cmd[0] == '-i':
-                    current_file = src_cmd[1]
-                else:
-                    current_file = src_cmd[3]
+                current_file = src_cmd[src_cmd.index('-i') + 1]
 
                 messenger.info('Play: "{}"'.format(current_file))
+                _current.src = current_file
+                _current.start = get_time(None)
+                webhook('clip_start', source=current_file,
+                        category=get_source.category)
 
                 with Popen([
-                    'ffmpeg', '-v', _log.ff_level.lower(), '-hide_banner',
-                    '-nostats'] + src_cmd + ff_pre_settings,
+                    'ffmpeg', '-v', ff_log_level(decoder_logger),
+                    '-hide_banner', '-nostats'] + src_cmd + ff_pre_settings,
                         stdout=PIPE, stderr=PIPE) as _ff.decoder:
 
                     dec_err_thread = Thread(target=ffmpeg_stderr_reader,
@@ -120,22 +146,42 @@ def main():
                     dec_err_thread.daemon = True
                     dec_err_thread.start()
 
+                    first_buf = True
+
                     while True:
                         buf = _ff.decoder.stdout.read(COPY_BUFSIZE)
+                        if first_buf:
+                            stage_timer.mark('spawn')
+                            stage_timer.report(current_file)
+                            first_buf = False
                         if not buf:
                             break
                         _ff.encoder.stdin.write(buf)
 
+                if _current.skip:
+                    _current.skip = False
+                elif os.path.isfile(current_file):
+                    if _ff.decoder.returncode == 0 and \
+                            not _current.probe_error:
+                        quarantine.passed(current_file)
+                    else:
+                        quarantine.failed(current_file)
+
+                _current.probe_error = False
+
         except BrokenPipeError:
             messenger.error('Broken Pipe!')
+            webhook('encoder_error')
             terminate_processes(watcher)
 
         except SystemExit:
             messenger.info('Got close command')
+            webhook('playout_stop')
             terminate_processes(watcher)
 
         except KeyboardInterrupt:
             messenger.warning('Program terminated')
+            webhook('playout_stop')
             terminate_processes(watcher)
 
         # close encoder when nothing is to do anymore
@@ -147,6 +193,11 @@ def main():
             _ff.encoder.terminate()
         _ff.encoder.wait()
 
+        if _ff.post_process:
+            if _ff.post_process.poll() is None:
+                _ff.post_process.terminate()
+            _ff.post_process.wait()
+
 
 if __name__ == '__main__':
     main()
@@ -0,0 +1,209 @@
+# -*- coding: utf-8 -*-
+
+# This file is part of ffplayout.
+#
+# ffplayout is free software: you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation, either version 3 of the License, or
+# (at your option) any later version.
+#
+# ffplayout is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with ffplayout. If not, see <http://www.gnu.org/licenses/>.
+
+# ------------------------------------------------------------------------------
+
+import hmac
+import json
+import logging
+import os
+import re
+import signal
+from http.server import BaseHTTPRequestHandler, HTTPServer
+from threading import Thread
+
+from .utils import (LOG_LEVELS, _control, _current, _ff, _init, _playlist,
+                    _playout, _text, daily_report, messenger, stdin_args)
+
+try:
+    import zmq
+except ImportError:
+    zmq = None
+
+
+# ------------------------------------------------------------------------------
+# minimal http control server,
+# for single channel setups without ffplayout-gui
+# ------------------------------------------------------------------------------
+
+def send_text(text):
+    """
+    send text to drawtext node over zmq
+    """
+    # escape special characters for the filter option parser
+    for char in ['\\', "'", ':']:
+        text = text.replace(char, '\\' + char)
+
+    context = zmq.Context()
+    socket = context.socket(zmq.REQ)
+    socket.setsockopt(zmq.LINGER, 0)
+    socket.setsockopt(zmq.RCVTIMEO, 2000)
+    socket.connect(_text.address)
+
+    try:
+        socket.send_string(
+            "Parsed_drawtext_2 reinit text={}".format(text))
+        return socket.recv_string()
+    finally:
+        socket.close()
+        context.term()
+
+
+class ControlHandler(BaseHTTPRequestHandler):
+    """
+    handle control requests, every request needs the token from config
+    in header: "Authorization: Bearer <token>"
+    """
+
+    def log_message(self, format, *args):
+        messenger.debug('Control request: {}'.format(format % args))
+
+    def send_json(self, code, data):
+        body = json.dumps(data).encode('utf-8')
+        self.send_response(code)
+        self.send_header('Content-Type', 'application/json')
+        self.send_header('Content-Length', str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+    def authorized(self):
+        auth = self.headers.get('Authorization', '')
+
+        if _control.token and hmac.compare_digest(
+                auth, 'Bearer {}'.format(_control.token)):
+            return True
+
+        self.send_json(401, {'error': 'unauthorized'})
+        return False
+
+    def read_json(self):
+        """
+        read json object from body, anything else gives an empty object
+        """
+        try:
+            length = int(self.headers.get('Content-Length', 0))
+        except ValueError:
+            return {}
+
+        if length <= 0:
+            return {}
+
+        try:
+            data = json.loads(self.rfile.read(length).decode('utf-8'))
+        except ValueError:
+            return {}
+
+        return data if isinstance(data, dict) else {}
+
+    def do_GET(self):
+        if not self.authorized():
+            return
+
+        if self.path == '/status':
+            self.send_json(200, {
+                'mode': 'playlist' if _playlist.mode and
+                        not stdin_args.folder else 'folder',
+                'source': _current.src,
+                'start': _current.start,
+                'drift': round(_current.drift, 3),
+                'encoder': _playout.encoder,
+                'warnings': _init.warnings,
+                'incidents': daily_report.incidents
+            })
+        else:
+            self.send_json(404, {'error': 'not found'})
+
+    def do_POST(self):
+        if not self.authorized():
+            return
+
+        if self.path == '/next':
+            if _playlist.mode and not stdin_args.folder:
+                self.send_json(400, {
+                    'error': 'next is only supported in folder mode'})
+            elif _ff.decoder and _ff.decoder.poll() is None:
+                messenger.info('Control: skip to next clip')
+                _current.skip = True
+                _ff.decoder.terminate()
+                self.send_json(200, {'message': 'next clip'})
+            else:
+                self.send_json(409, {'error': 'no clip is playing'})
+
+        elif self.path == '/text':
+            if zmq is None or not _text.add_text:
+                self.send_json(501, {
+                    'error': 'text needs add_text and python module pyzmq'})
+                return
+
+            try:
+                reply = send_text(str(self.read_json().get('text', '')))
+                self.send_json(200, {'message': reply})
+            except zmq.ZMQError as err:
+                self.send_json(502, {'error': str(err)})
+
+        elif self.path == '/loglevel':
+            data = self.read_json()
+            name = data.get('logger')
+            level = str(data.get('level', '')).upper()
+
+            if name not in ['playout', 'decoder', 'encoder'] or \
+                    level not in LOG_LEVELS:
+                self.send_json(400, {
+                    'error': 'logger must be playout, decoder or encoder, '
+                             'level one of: {}'.format(', '.join(LOG_LEVELS))})
+                return
+
+            messenger.info('Control: set {} log level to {}'.format(
+                name, level))
+            logging.getLogger(name).setLevel(level)
+            self.send_json(200, {'message': '{} level is {}'.format(
+                name, level)})
+
+        elif self.path == '/stop':
+            messenger.info('Control: stop playout')
+            self.send_json(200, {'message': 'stop playout'})
+            os.kill(os.getpid(), signal.SIGTERM)
+
+        else:
+            self.send_json(404, {'error': 'not found'})
+
+
+def start_control_server():
+    """
+    run control server in background thread
+    """
+    # token from a not existing environment variable stays unexpanded
+    if not _control.token or _control.token.strip() in ['', 'None'] or \
+            re.search(r'\$\{?\w+\}?', _control.token):
+        messenger.error('Control server needs a token, it is not started!')
+        return
+
+    # control server is optional, it must not stop the playout
+    try:
+        host, port = _control.address.rsplit(':', 1)
+        server = HTTPServer((host, int(port)), ControlHandler)
+    except (AttributeError, OSError, OverflowError, ValueError) as err:
+        messenger.error('Control server not started on "{}": {}'.format(
+            _control.address, err))
+        return
+
+    messenger.info('Control server listening on: {}'.format(
+        _control.address))
+
+    thread = Thread(name='control', target=server.serve_forever)
+    thread.daemon = True
+    thread.start()
@@ -65,11 +65,12 @@ def pad_filter(probe):
 
 def fps_filter(probe):
     """
-    changing frame rate
+    changing frame rate,
+    variable frame rate sources get always a constant frame rate
     """
     filter_chain = []
 
-    if probe.video[0]['fps'] != _pre_comp.fps:
+    if probe.video[0]['fps'] != _pre_comp.fps or probe.video[0]['vfr']:
         filter_chain.append('fps={}'.format(_pre_comp.fps))
 
     return filter_chain
@@ -93,22 +94,83 @@ def scale_filter(probe):
     return filter_chain
 
 
+def subtitle_filter(subtitle, seek, msg):
+    """
+    burn in subtitle file from playlist,
+    ass files have own styles, other formats get style from config,
+    when clip is cutted at the begin, subtitle timestamps are shifted
+    """
+    filter_chain = []
+
+    if not subtitle:
+        return filter_chain
+
+    if not os.path.isfile(subtitle):
+        msg.warning('Subtitle "{}" not exist!'.format(subtitle))
+        return filter_chain
+
+    path = subtitle.replace('\\', '/').replace(':', '\\:')
+
+    if seek > 0.0:
+        filter_chain.append('setpts=PTS+{}/TB'.format(seek))
+
+    if subtitle.lower().endswith('.ass'):
+        filter_chain.append("ass='{}'".format(path))
+    elif _pre_comp.subtitle_style:
+        filter_chain.append("subtitles='{}':force_style='{}'".format(
+            path, _pre_comp.subtitle_style))
+    else:
+        filter_chain.append("subtitles='{}'".format(path))
+
+    if seek > 0.0:
+        filter_chain.append('setpts=PTS-STARTPTS')
+
+    return filter_chain
+
+
 def fade_filter(duration, seek, out, track=''):
     """
-    fade in/out video, when is cutted at the begin or end
+    fade in/out video, when is cutted at the begin or end,
+    with clip_fade from config every clip fades in and out,
+    on short clips a fade takes max half of the clip
     """
     filter_chain = []
+    fade_in = fade_out = _pre_comp.clip_fade or 0.0
+    length = max(out - seek, 0.0)
 
     if seek > 0.0:
-        filter_chain.append('{}fade=in:st=0:d=0.5'.format(track))
+        fade_in = max(fade_in, 0.5)
 
     if out != duration:
-        filter_chain.append('{}fade=out:st={}:d=1.0'.format(track,
-                                                            out - seek - 1.0))
+        fade_out = max(fade_out, 1.0)
+
+    fade_in = min(fade_in, length / 2)
+    fade_out = min(fade_out, length / 2)
+
+    if fade_in:
+        filter_chain.append('{}fade=in:st=0:d={}'.format(track, fade_in))
+
+    if fade_out:
+        filter_chain.append('{}fade=out:st={}:d={}'.format(
+            track, max(length - fade_out, 0.0), fade_out))
 
     return filter_chain
 
 
+def tempo_filter(tempo, track=''):
+    """
+    play clip a bit faster to catch up a delay,
+    added after fades, so they keep the position from the clip
+    """
+    if tempo == 1.0:
+        return []
+
+    if track == 'a':
+        return ['atempo={}'.format(tempo)]
+
+    return ['setpts=PTS/{}'.format(tempo)]
+
+
 def overlay_filter(duration, ad, ad_last, ad_next):
     """
     overlay logo: when is an ad don't overlay,
@@ -136,6 +198,60 @@ def overlay_filter(duration, ad, ad_last, ad_next):
     return logo_filter
 
 
+def extra_logo_filter(logo_filter, seek, ad):
+    """
+    overlay extra logos from config over the main logo,
+    every logo can have a time window, counted from begin of the clip,
+    windows which are cutted away by seek are skipped
+    """
+    extras = []
+
+    for logo in _pre_comp.extra_logos or []:
+        start = logo.get('start') or 0
+        length = logo.get('length')
+
+        if ad or not os.path.isfile(logo['logo']) or (
+                length and start + length <= seek):
+            continue
+
+        if length:
+            enable = 'between(t,{},{})'.format(
+                max(start - seek, 0), start + length - seek)
+        else:
+            enable = 'gte(t,{})'.format(max(start - seek, 0))
+
+        extras.append((logo, enable))
+
+    if not extras:
+        return logo_filter
+
+    chain = [logo_filter[:-len('[logo]')] + '[lg0]']
+
+    for i, (logo, enable) in enumerate(extras):
+        out = '[logo]' if i == len(extras) - 1 else '[lg{}]'.format(i + 1)
+        chain.append(
+            "movie={},loop=loop=-1:size=1:start=0[el{}];[lg{}][el{}]"
+            "{}:shortest=1:enable='{}'{}".format(
+                logo['logo'], i, i, i, logo.get('filter', 'overlay=0:0'),
+                enable, out))
+
+    return ';'.join(chain)
+
+
+def select_audio(probe):
+    """
+    select audio track by preferred languages from config,
+    fallback is the first audio track
+    """
+    for lang in _pre_comp.audio_languages or []:
+        for index, stream in enumerate(probe.audio):
+            tags = stream.get('tags', {})
+            if tags.get('language', '').lower() == lang.lower():
+                return index
+
+    return 0
+
+
 def add_audio(probe, duration, msg):
     """
     when clip has no audio we generate an audio line
@@ -151,6 +267,62 @@ def add_audio(probe, duration, msg):
     return line
 
 
+def audio_sync(probe):
+    """
+    when video has a variable frame rate,
+    resample audio to keep it in sync with video
+    """
+    sync_filter = []
+
+    if probe.audio and probe.video[0] and probe.video[0]['vfr']:
+        sync_filter.append('aresample=async=1')
+
+    return sync_filter
+
+
+def audio_only_video(probe, duration):
+    """
+    generate video for audio only clips, from the waveform, spectrum,
+    a still image, or black, so targets which need video get it
+    """
+    size = '{}x{}'.format(_pre_comp.w, _pre_comp.h)
+    mode = _pre_comp.audio_only_video
+    track = '[0:a:{}]'.format(select_audio(probe))
+
+    if mode == 'waves':
+        return '{}showwaves=s={}:mode=cline:rate={}'.format(
+            track, size, _pre_comp.fps)
+    elif mode == 'spectrum':
+        return '{}showspectrum=s={}:slide=scroll,fps={}'.format(
+            track, size, _pre_comp.fps)
+    elif mode and os.path.isfile(mode):
+        return ('movie={},loop=loop=-1:size=1:start=0,scale={}:{},'
+                'setsar=1,fps={},trim=duration={}').format(
+                    mode, _pre_comp.w, _pre_comp.h, _pre_comp.fps, duration)
+
+    return 'color=c=black:s={}:r={}:d={}'.format(size, _pre_comp.fps,
+                                                 duration)
+
+
+def gain_filter(probe, gain, msg):
+    """
+    change volume by gain in dB from playlist, like from ReplayGain tags
+    """
+    if not probe.audio or gain is None:
+        return []
+
+    try:
+        value = float(str(gain).lower().replace('db', '').strip())
+    except ValueError:
+        msg.warning('Gain "{}" is not a number'.format(gain))
+        return []
+
+    if value == 0.0:
+        return []
+
+    return ['volume={}dB'.format(value)]
+
+
 def add_loudnorm(probe):
     """
     add single pass loudnorm filter to audio line
@@ -164,6 +336,21 @@ def add_loudnorm(probe):
     return loud_filter
 
 
+def compressor_filter(probe, category):
+    """
+    add dynamic range compression by clip category,
+    'default' is used for clips without own category setting
+    """
+    compressor = _pre_comp.compressor or {}
+    comp_filter = compressor.get(category or 'default',
+                                 compressor.get('default'))
+
+    if probe.audio and comp_filter:
+        return [comp_filter]
+
+    return []
+
+
 def extend_audio(probe, duration):
     """
     check audio duration, is it shorter then clip duration - pad it
@@ -192,41 +379,59 @@ def extend_video(probe, duration, target_duration):
     return pad_filter
 
 
-def build_filtergraph(duration, seek, out, ad, ad_last, ad_next, probe, msg):
+def build_filtergraph(duration, seek, out, ad, ad_last, ad_next, probe, msg,
+                      subtitle=None, category=None, tempo=1.0, gain=None):
     """
     build final filter graph, with video and audio chain
     """
     video_chain = []
     audio_chain = []
+    video_source = None
     video_map = ['-map', '[logo]']
 
     if out > duration:
         seek = 0
 
+    # length after tempo change, for generated sources and logo fade
+    play_length = (out - seek) / tempo
+
     if probe.video[0]:
         video_chain += deinterlace_filter(probe)
         video_chain += pad_filter(probe)
         video_chain += fps_filter(probe)
         video_chain += scale_filter(probe)
+        video_chain += subtitle_filter(subtitle, seek, msg)
         video_chain += extend_video(probe, duration, out - seek)
         video_chain += fade_filter(duration, seek, out)
 
-        audio_chain += add_audio(probe, out - seek, msg)
+        audio_chain += add_audio(probe, play_length, msg)
+    elif probe.audio and probe.audio[0]:
+        video_source = audio_only_video(probe, out - seek)
+        video_chain += fade_filter(duration, seek, out)
+
+    if (probe.video[0] or video_source) and not audio_chain:
+        audio_chain.append('[0:a:{}]anull'.format(select_audio(probe)))
+        audio_chain += audio_sync(probe)
+        audio_chain += gain_filter(probe, gain, msg)
+        audio_chain += compressor_filter(probe, category)
+        audio_chain += add_loudnorm(probe)
+        audio_chain += extend_audio(probe, out - seek)
+        audio_chain += fade_filter(duration, seek, out, 'a')
+        audio_chain += tempo_filter(tempo, 'a')
 
-        if not audio_chain:
-            audio_chain.append('[0:a]anull')
-            audio_chain += add_loudnorm(probe)
-            audio_chain += extend_audio(probe, out - seek)
-            audio_chain += fade_filter(duration, seek, out, 'a')
+    if probe.video[0] or video_source:
+        video_chain += tempo_filter(tempo)
 
     if video_chain:
         video_filter = '{}[v]'.format(','.join(video_chain))
     else:
         video_filter = 'null[v]'
 
-    logo_filter = overlay_filter(out - seek, ad, ad_last, ad_next)
+    logo_filter = overlay_filter(play_length, ad, ad_last, ad_next)
+    logo_filter = extra_logo_filter(logo_filter, seek, ad)
     video_filter = [
-        '-filter_complex', '[0:v]{};{}'.format(
+        '-filter_complex', '{}{};{}'.format(
+            video_source + ',' if video_source else '[0:v]',
             video_filter, logo_filter)]
 
     if audio_chain:
@@ -237,7 +442,7 @@ def build_filtergraph(duration, seek, out, ad, ad_last, ad_next, probe, msg):
         audio_filter = []
         audio_map = ['-map', '0:a']
 
-    if probe.video[0]:
+    if probe.video[0] or video_source:
         return video_filter + audio_filter + video_map + audio_map
     else:
         return video_filter + video_map + ['-map', '1:a']
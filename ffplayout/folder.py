@@ -18,6 +18,7 @@
 # ------------------------------------------------------------------------------
 
 import glob
+import json
 import os
 import random
 import time
@@ -26,7 +27,8 @@ from watchdog.events import PatternMatchingEventHandler
 from watchdog.observers import Observer
 
 from .filters import build_filtergraph
-from .utils import MediaProbe, _storage, messenger, stdin_args
+from .utils import (MediaProbe, _general, _storage, gen_dummy, messenger,
+                    quarantine, stage_timer, stdin_args)
 
 
 # ------------------------------------------------------------------------------
@@ -41,6 +43,9 @@ class MediaStore:
 
     def __init__(self):
         self.store = []
+        self.start_index = 0
+        self.state_file = os.path.join(_general.state_path,
+                                       'ffplayout_folder.json')
 
         if stdin_args.folder:
             self.folder = stdin_args.folder
@@ -57,9 +62,65 @@ class MediaStore:
 
         if _storage.shuffle:
             self.rand()
+            self.restore()
         else:
             self.sort()
 
+    def restore(self):
+        """
+        take over shuffle order and position from last run,
+        so a restart not reshuffle and repeat recently played clips,
+        a broken state file keeps the fresh shuffle
+        """
+        if not os.path.isfile(self.state_file):
+            return
+
+        try:
+            with open(self.state_file, 'r', encoding='utf-8') as f:
+                state = json.load(f)
+        except (OSError, ValueError):
+            return
+
+        if not isinstance(state, dict) or \
+                state.get('folder') != self.folder:
+            return
+
+        last_state = state.get('store')
+        index = state.get('index')
+
+        if not isinstance(last_state, list) or \
+                not isinstance(index, int) or isinstance(index, bool):
+            messenger.warning('Folder state is not valid, ignore it: '
+                              '"{}"'.format(self.state_file))
+            return
+
+        current = set(self.store)
+        last_store = [f for f in last_state
+                      if isinstance(f, str) and f in current]
+        last_set = set(last_store)
+        new_files = [f for f in self.store if f not in last_set]
+
+        if 0 <= index < len(last_state) and last_state[index] in last_store:
+            self.start_index = last_store.index(last_state[index])
+
+        self.store = last_store + new_files
+
+        messenger.info('Resume folder order from last run')
+
+    def save(self, index):
+        """
+        save shuffle order and current position
+        """
+        if not _storage.shuffle:
+            return
+
+        try:
+            with open(self.state_file, 'w', encoding='utf-8') as f:
+                json.dump({'folder': self.folder, 'index': index,
+                           'store': self.store}, f)
+        except OSError as err:
+            messenger.error('Saving folder state failed: {}'.format(err))
+
     def add(self, file):
         self.store.append(file)
         self.sort()
@@ -135,17 +196,69 @@ class GetSourceFromFolder:
         self._media = media
 
         self.last_played = []
-        self.index = 0
+        self.index = media.start_index
+        self.category = None
         self.probe = MediaProbe()
+        # clips which are not playable in this round, with modification time,
+        # so they are reported once and an empty round can wait for changes
+        self.skipped = {}
+
+    def skip(self, src, message):
+        """
+        report not playable clip only once, until it is changed
+        """
+        try:
+            mod_time = os.path.getmtime(src)
+        except OSError:
+            mod_time = None
+
+        if self.skipped.get(src, False) != mod_time:
+            self.skipped[src] = mod_time
+
+            if message:
+                messenger.warning(message)
+
+    def is_skipped(self, src):
+        try:
+            return self.skipped.get(src, False) == os.path.getmtime(src)
+        except OSError:
+            return src in self.skipped
 
     def next(self):
         while True:
+            played = False
+            blocked = quarantine.blocked()
+
             while self.index < len(self._media.store):
-                self.probe.load(self._media.store[self.index])
+                src = self._media.store[self.index]
+
+                if src in blocked:
+                    self.skip(src, 'Clip is in quarantine, skip: "{}"'.format(
+                        src))
+                    self.index += 1
+                    continue
+
+                self._media.save(self.index)
+                stage_timer.reset()
+                self.probe.load(src)
+                stage_timer.mark('probe')
+
+                if not self.probe.format:
+                    if self.probe.error:
+                        quarantine.failed(src)
+
+                    # probe logs the error already
+                    self.skip(src, None)
+                    self.index += 1
+                    continue
+
+                played = True
+
                 filtergraph = build_filtergraph(
                     float(self.probe.format['duration']), 0.0,
                     float(self.probe.format['duration']), False, False,
                     False, self.probe, messenger)
+                stage_timer.mark('filter')
 
                 yield [
                     '-i', self._media.store[self.index]
@@ -153,3 +266,28 @@ class GetSourceFromFolder:
                 self.index += 1
             else:
                 self.index = 0
+
+                if played:
+                    # try failed clips again in next round
+                    self.skipped = {}
+                else:
+                    # nothing playable in folder, wait with a dummy clip
+                    # for new or changed files
+                    messenger.error(
+                        'No playable clip in folder, play dummy: "{}"'.format(
+                            self._media.folder))
+                    self.probe.load(None)
+
+                    while not self.playable():
+                        yield gen_dummy(30.0) + build_filtergraph(
+                            30.0, 0.0, 30.0, False, False, False,
+                            self.probe, messenger)
+
+    def playable(self):
+        """
+        check for clips which are not in quarantine and not skipped
+        """
+        blocked = quarantine.blocked()
+
+        return [src for src in self._media.store
+                if src not in blocked and not self.is_skipped(src)]
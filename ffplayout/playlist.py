@@ -18,16 +18,20 @@
 # ------------------------------------------------------------------------------
 
 import os
+import shlex
 import socket
 import ssl
 import time
+from datetime import datetime, timedelta
 from urllib import request
 
 from ffplayout.filters import build_filtergraph
-from ffplayout.utils import (MediaProbe, _playlist, gen_filler, get_date,
-                             get_delta, get_time, is_float, messenger,
-                             stdin_args, timed_source, valid_json,
-                             validate_thread)
+from ffplayout.utils import (MediaProbe, _current, _playlist, gen_filler,
+                             get_date, get_delta, get_time, messenger,
+                             playlist_path, read_playlist, stage_timer,
+                             stdin_args, time_to_sec, timed_source,
+                             valid_json, validate_playlist_file,
+                             validate_thread, webhook)
 
 
 class GetSourceFromPlaylist:
@@ -58,6 +62,7 @@ class GetSourceFromPlaylist:
         self.first = True
         self.last = False
         self.list_date = get_date(True)
+        self.checked_date = None
 
         self.src = None
         self.begin = 0
@@ -67,14 +72,16 @@ class GetSourceFromPlaylist:
         self.ad = False
         self.ad_last = False
         self.ad_next = False
+        self.subtitle = None
+        self.category = None
+        self.tempo = 1.0
+        self.gain = None
 
     def get_playlist(self):
         if stdin_args.playlist:
             self.json_file = stdin_args.playlist
         else:
-            year, month, day = self.list_date.split('-')
-            self.json_file = os.path.join(
-             _playlist.path, year, month, self.list_date + '.json')
+            self.json_file = playlist_path(self.list_date)
 
         if '://' in self.json_file:
             self.json_file = self.json_file.replace('\\', '/')
@@ -93,14 +100,14 @@ class GetSourceFromPlaylist:
                     messenger.info('Open: ' + self.json_file)
                     validate_thread(self.clip_nodes)
             except (request.URLError, socket.timeout):
+                webhook('playlist_missing', playlist=self.json_file)
                 self.eof_handling('Get playlist from url failed!', False)
 
         elif os.path.isfile(self.json_file):
             # check last modification from playlist
             mod_time = os.path.getmtime(self.json_file)
             if mod_time > self.last_mod_time:
-                with open(self.json_file, 'r', encoding='utf-8') as f:
-                    self.clip_nodes = valid_json(f)
+                self.clip_nodes = read_playlist(self.json_file)
 
                 self.last_mod_time = mod_time
                 messenger.info('Open: ' + self.json_file)
@@ -109,21 +116,47 @@ class GetSourceFromPlaylist:
             # when we have no playlist for the current day,
             # then we generate a black clip
             # and calculate the seek in time, for when the playlist comes back
+            webhook('playlist_missing', playlist=self.json_file)
             self.eof_handling('Playlist not exist:', False)
 
+    def check_next_playlist(self):
+        # validate playlist from next day, before the current one ends,
+        # so a missing or broken playlist is reported in time
+        if stdin_args.playlist or self.checked_date == self.list_date:
+            return
+
+        self.checked_date = self.list_date
+        next_date = (datetime.strptime(self.list_date, '%Y-%m-%d')
+                     + timedelta(1)).strftime('%Y-%m-%d')
+        next_file = playlist_path(next_date)
+
+        if '://' in next_file:
+            return
+
+        if os.path.isfile(next_file):
+            messenger.info('Check next playlist: ' + next_file)
+            validate_playlist_file(next_file)
+        else:
+            messenger.error('Next playlist not exist: ' + next_file)
+            webhook('playlist_missing', playlist=next_file)
+
     def get_clip_in_out(self, node):
-        if is_float(node["in"]):
-            self.seek = node["in"]
+        seek = time_to_sec(node["in"])
+        duration = time_to_sec(node["duration"])
+        out = time_to_sec(node["out"])
+
+        if seek is not None:
+            self.seek = seek
         else:
             self.seek = 0
 
-        if is_float(node["duration"]):
-            self.duration = node["duration"]
+        if duration is not None:
+            self.duration = duration
         else:
             self.duration = 20
 
-        if is_float(node["out"]):
-            self.out = node["out"]
+        if out is not None:
+            self.out = out
         else:
             self.out = self.duration
 
@@ -132,8 +165,18 @@ class GetSourceFromPlaylist:
             self.probe, self.src, self.begin, self.duration,
             self.seek, self.out, self.first, self.last
         )
+        self.tempo = _current.tempo
+
+    def set_input_param(self, node):
+        # add extra input parameters from playlist, only for this clip
+        if 'input_param' in node and self.src_cmd and \
+                self.src in self.src_cmd:
+            index = self.src_cmd.index(self.src) - 1
+            self.src_cmd[index:index] = shlex.split(node['input_param'])
 
     def get_category(self, index, node):
+        self.category = node.get('category')
+
         if 'category' in node:
             if index - 1 >= 0:
                 last_category = self.clip_nodes[
@@ -165,7 +208,8 @@ class GetSourceFromPlaylist:
     def set_filtergraph(self):
         self.filtergraph = build_filtergraph(
             self.duration, self.seek, self.out, self.ad, self.ad_last,
-            self.ad_next, self.probe, messenger)
+            self.ad_next, self.probe, messenger, self.subtitle,
+            self.category, self.tempo, self.gain)
 
     def check_for_next_playlist(self):
         if not self.next_playlist:
@@ -184,6 +228,10 @@ class GetSourceFromPlaylist:
     def eof_handling(self, message, fill):
         self.seek = 0.0
         self.ad = False
+        self.subtitle = None
+        self.category = None
+        self.tempo = 1.0
+        self.gain = None
 
         current_delta, total_delta = get_delta(self.begin)
 
@@ -207,16 +255,26 @@ class GetSourceFromPlaylist:
     def peperation_task(self, index, node):
         # call functions in order to prepare source and filter
         self.src = node["source"]
+        self.subtitle = node.get('subtitle')
+        self.gain = node.get('gain')
         self.probe.load(self.src)
+        stage_timer.mark('probe')
+        # counts for the quarantine together with the decoder exit code
+        _current.probe_error = self.probe.error
 
         self.get_input()
+        self.set_input_param(node)
+        stage_timer.mark('input')
         self.get_category(index, node)
         self.set_filtergraph()
+        stage_timer.mark('filter')
         self.check_for_next_playlist()
 
     def next(self):
         while True:
+            stage_timer.reset()
             self.get_playlist()
+            stage_timer.mark('playlist')
 
             if self.clip_nodes is None:
                 self.eof_handling('Playlist is empty!', True)
@@ -239,6 +297,7 @@ class GetSourceFromPlaylist:
                 elif self.last_time < self.begin:
                     if index + 1 == len(self.clip_nodes["program"]):
                         self.last = True
+                        self.check_next_playlist()
                     else:
                         self.last = False
 
@@ -17,16 +17,21 @@
 
 # ------------------------------------------------------------------------------
 
+import csv
+import gzip
 import json
 import logging
 import math
 import os
 import re
+import shutil
 import signal
 import smtplib
 import socket
 import sys
 import tempfile
+import time
+import xml.etree.ElementTree as ET
 import yaml
 from argparse import ArgumentParser
 from datetime import date, datetime, timedelta
@@ -34,9 +39,11 @@ from email.mime.multipart import MIMEMultipart
 from email.mime.text import MIMEText
 from email.utils import formatdate
 from logging.handlers import TimedRotatingFileHandler
-from subprocess import CalledProcessError, check_output
-from threading import Thread
+from subprocess import STDOUT, CalledProcessError, check_output
+from threading import Lock, Thread
 from types import SimpleNamespace
+from urllib import request
+from urllib.parse import unquote, urlparse
 
 
 # ------------------------------------------------------------------------------
@@ -117,22 +124,90 @@ _pre_comp = SimpleNamespace()
 _playlist = SimpleNamespace()
 _storage = SimpleNamespace()
 _text = SimpleNamespace()
+_webhook = SimpleNamespace()
+_accept = SimpleNamespace()
+_dummy = SimpleNamespace()
 _playout = SimpleNamespace()
+_control = SimpleNamespace()
 
-_init = SimpleNamespace(load=True)
-_ff = SimpleNamespace(decoder=None, encoder=None)
+_init = SimpleNamespace(load=True, warnings=[], errors=[], unknown=[])
+_ff = SimpleNamespace(decoder=None, encoder=None, post_process=None)
+_current = SimpleNamespace(src=None, start=None, skip=False, drift=0.0,
+                           tempo=1.0, probe_error=False)
 
 
-def str_to_sec(s):
+def time_to_sec(value):
+    """
+    convert time value to seconds, allowed are numbers
+    and strings in format: "HH:MM:SS.ms", "MM:SS.ms" or "SS.ms",
+    comma as decimal separator is also allowed,
+    on invalid value return None
+    """
+    if value is None or isinstance(value, bool):
+        return None
+    elif isinstance(value, (int, float)):
+        return float(value)
+
+    seconds = 0.0
+    parts = str(value).strip().replace(',', '.').split(':')
+
+    if len(parts) > 3:
+        return None
+
+    try:
+        for part in parts:
+            seconds = seconds * 60 + float(part)
+    except ValueError:
+        return None
+
+    return seconds
+
+
+def sec_to_time(seconds):
+    """
+    format seconds to time string: "HH:MM:SS.ms"
+    """
+    millis = int(round(abs(seconds) * 1000))
+    hours, millis = divmod(millis, 3600000)
+    minutes, millis = divmod(millis, 60000)
+
+    return '{}{:02d}:{:02d}:{:06.3f}'.format(
+        '-' if seconds < 0 else '', hours, minutes, millis / 1000)
+
+
+def str_to_sec(s, key):
     if s in ['now', '', None, 'none']:
         return None
     else:
-        s = s.split(':')
-        try:
-            return float(s[0]) * 3600 + float(s[1]) * 60 + float(s[2])
-        except ValueError:
-            print('Wrong time format!')
-            sys.exit(1)
+        seconds = time_to_sec(s)
+
+        if seconds is None:
+            _init.errors.append('wrong time format in "{}": {}'.format(
+                key, s))
+
+        return seconds
+
+
+def to_float(value, key):
+    """
+    convert number from config to float, allow comma as decimal separator
+    and percent values, like "70%" for 0.7,
+    wrong values are collected for the startup check and give 0
+    """
+    if isinstance(value, (int, float)) and not isinstance(value, bool):
+        return float(value)
+
+    text = str(value).strip().replace(',', '.')
+
+    try:
+        if text.endswith('%'):
+            return float(text[:-1]) / 100
+
+        return float(text)
+    except ValueError:
+        _init.errors.append('wrong number format in "{}": {}'.format(
+            key, value))
+        return 0.0
 
 
 def read_config(path):
@@ -140,17 +215,118 @@ def read_config(path):
         return yaml.safe_load(config_file)
 
 
+def expand_vars(value):
+    """
+    expand environment variables like ${NAME}
+    and replace placeholders like {station_name} with values
+    from config variables, unknown placeholders stay untouched,
+    blank values stay None
+    """
+    if value is None:
+        return None
+
+    variables = _general.variables or {}
+
+    return re.sub(r'\{(\w+)\}',
+                  lambda m: str(variables.get(m.group(1), m.group(0))),
+                  os.path.expandvars(str(value)))
+
+
 def dict_to_list(d):
     li = []
 
     for key, value in d.items():
         if value:
-            li += ['-{}'.format(key), str(value)]
+            li += ['-{}'.format(key), expand_vars(value)]
         else:
             li += ['-{}'.format(key)]
     return li
 
 
+CONFIG_KEYS = {
+    'general': ['stop_on_error', 'stop_threshold', 'drift_correction',
+                'drift_tempo', 'validation_load', 'quarantine_threshold',
+                'state_path', 'variables'],
+    'mail': ['subject', 'smpt_server', 'smpt_port', 'sender_addr',
+             'sender_pass', 'recipient', 'mail_level', 'daily_report'],
+    'logging': ['log_to_file', 'log_path', 'log_level', 'ffmpeg_level',
+                'backup_count', 'compress_backup', 'profiling'],
+    'pre_compress': ['width', 'height', 'aspect', 'fps', 'add_logo', 'logo',
+                     'logo_opacity', 'logo_filter', 'extra_logos',
+                     'add_loudnorm', 'loud_I', 'loud_TP', 'loud_LRA',
+                     'audio_languages', 'subtitle_style', 'clip_fade',
+                     'compressor', 'audio_only_video'],
+    'playlist': ['playlist_mode', 'path', 'day_start', 'length',
+                 'min_clip_length', 'short_clip', 'csv_columns'],
+    'acceptance': ['min_width', 'min_height', 'min_duration', 'video_codecs',
+                   'audio_codecs'],
+    'storage': ['path', 'filler_path', 'filler_clip', 'extensions',
+                'shuffle', 'filler_threshold'],
+    'dummy': ['video', 'color', 'image', 'clock', 'audio'],
+    'webhooks': ['urls'],
+    'text': ['add_text', 'bind_address', 'fontfile'],
+    'control': ['enable', 'bind_address', 'token'],
+    'out': ['preview', 'service_name', 'service_provider',
+            'post_ffmpeg_param', 'out_addr', 'backup_addr', 'post_process',
+            'extra_outputs']
+}
+
+
+def check_extra_logos(logos):
+    """
+    check extra logos from config and convert start and length to seconds,
+    wrong items are collected for the startup check and not used
+    """
+    valid = []
+
+    if logos and not isinstance(logos, list):
+        _init.errors.append('extra_logos must be a list')
+        return valid
+
+    for logo in logos or []:
+        if not isinstance(logo, dict) or not logo.get('logo'):
+            _init.errors.append(
+                'extra_logos item needs a logo: {}'.format(logo))
+            continue
+
+        start = time_to_sec(logo.get('start') or 0)
+        length = logo.get('length')
+
+        if length not in [None, '']:
+            length = time_to_sec(length)
+
+            if length is None:
+                start = None
+        else:
+            length = None
+
+        if start is None:
+            _init.errors.append(
+                'wrong start or length in extra_logos: {}'.format(logo))
+            continue
+
+        valid.append(dict(logo, start=start, length=length))
+
+    return valid
+
+
+def unknown_config_keys(cfg):
+    """
+    list settings which this version does not use,
+    like from an older or a newer config file
+    """
+    unknown = []
+
+    for section, values in cfg.items():
+        if section not in CONFIG_KEYS:
+            unknown.append(section)
+        elif isinstance(values, dict):
+            unknown += ['{}.{}'.format(section, key) for key in values
+                        if key not in CONFIG_KEYS[section]]
+
+    return unknown
+
+
 def load_config():
     """
     this function can reload most settings from configuration file,
@@ -165,21 +341,39 @@ def load_config():
     else:
         cfg = read_config('ffplayout.yml')
 
+    # sections and keys which are newer then the first config version
+    # are optional, so an older config still runs with default values
+    _init.errors = []
+    _init.unknown = unknown_config_keys(cfg)
+    dummy = cfg.get('dummy') or {}
+    accept = cfg.get('acceptance') or {}
+
     if stdin_args.start:
-        p_start = str_to_sec(stdin_args.start)
+        p_start = str_to_sec(stdin_args.start, 'start')
     else:
-        p_start = str_to_sec(cfg['playlist']['day_start'])
+        p_start = str_to_sec(cfg['playlist']['day_start'], 'day_start')
 
     if not p_start:
         p_start = get_time('full_sec')
 
     if stdin_args.length:
-        p_length = str_to_sec(stdin_args.length)
+        p_length = str_to_sec(stdin_args.length, 'length')
     else:
-        p_length = str_to_sec(cfg['playlist']['length'])
+        p_length = str_to_sec(cfg['playlist']['length'], 'length')
 
     _general.stop = cfg['general']['stop_on_error']
-    _general.threshold = cfg['general']['stop_threshold']
+    _general.threshold = to_float(cfg['general']['stop_threshold'],
+                                  'stop_threshold')
+    _general.quarantine = to_float(
+        cfg['general'].get('quarantine_threshold') or 0,
+        'quarantine_threshold')
+    _general.variables = cfg['general'].get('variables')
+    _general.drift = to_float(cfg['general'].get('drift_correction') or 0,
+                              'drift_correction')
+    _general.tempo = to_float(cfg['general'].get('drift_tempo') or 0,
+                              'drift_tempo')
+    _general.max_load = to_float(cfg['general'].get('validation_load') or 0,
+                                 'validation_load')
 
     _mail.subject = cfg['mail']['subject']
     _mail.server = cfg['mail']['smpt_server']
@@ -188,52 +382,146 @@ def load_config():
     _mail.s_pass = cfg['mail']['sender_pass']
     _mail.recip = cfg['mail']['recipient']
     _mail.level = cfg['mail']['mail_level']
+    _mail.report = cfg['mail'].get('daily_report', False)
 
     _pre_comp.add_logo = cfg['pre_compress']['add_logo']
     _pre_comp.logo = cfg['pre_compress']['logo']
-    _pre_comp.opacity = cfg['pre_compress']['logo_opacity']
-    _pre_comp.logo_filter = cfg['pre_compress']['logo_filter']
+    _pre_comp.opacity = to_float(cfg['pre_compress']['logo_opacity'],
+                                 'logo_opacity')
+    _pre_comp.logo_filter = expand_vars(
+        cfg['pre_compress']['logo_filter'] or 'overlay=W-w-12:12')
+    _pre_comp.extra_logos = check_extra_logos(
+        cfg['pre_compress'].get('extra_logos'))
     _pre_comp.add_loudnorm = cfg['pre_compress']['add_loudnorm']
-    _pre_comp.loud_i = cfg['pre_compress']['loud_I']
-    _pre_comp.loud_tp = cfg['pre_compress']['loud_TP']
-    _pre_comp.loud_lra = cfg['pre_compress']['loud_LRA']
+    _pre_comp.loud_i = to_float(cfg['pre_compress']['loud_I'], 'loud_I')
+    _pre_comp.loud_tp = to_float(cfg['pre_compress']['loud_TP'], 'loud_TP')
+    _pre_comp.loud_lra = to_float(cfg['pre_compress']['loud_LRA'],
+                                  'loud_LRA')
+    _pre_comp.audio_languages = cfg['pre_compress'].get('audio_languages')
+    _pre_comp.subtitle_style = cfg['pre_compress'].get('subtitle_style')
+    _pre_comp.clip_fade = to_float(
+        cfg['pre_compress'].get('clip_fade') or 0, 'clip_fade')
+    _pre_comp.compressor = cfg['pre_compress'].get('compressor')
+    _pre_comp.audio_only_video = cfg['pre_compress'].get(
+        'audio_only_video', 'waves')
 
     _playlist.mode = cfg['playlist']['playlist_mode']
     _playlist.path = cfg['playlist']['path']
     _playlist.start = p_start
     _playlist.length = p_length
+    _playlist.min_length = time_to_sec(
+        cfg['playlist'].get('min_clip_length') or 0)
+    _playlist.short_clip = cfg['playlist'].get('short_clip', 'merge')
+
+    if _playlist.min_length is None:
+        _init.errors.append(
+            'wrong time format in "min_clip_length": {}'.format(
+                cfg['playlist'].get('min_clip_length')))
+        _playlist.min_length = 0.0
+
+    if _playlist.min_length and \
+            _playlist.short_clip not in ['skip', 'pad', 'merge']:
+        _init.errors.append(
+            'short_clip must be skip, pad or merge: {}'.format(
+                _playlist.short_clip))
+    _playlist.csv_columns = cfg['playlist'].get('csv_columns')
 
     _storage.path = cfg['storage']['path']
     _storage.filler = cfg['storage']['filler_clip']
     _storage.extensions = cfg['storage']['extensions']
     _storage.shuffle = cfg['storage']['shuffle']
+    _storage.filler_threshold = to_float(
+        cfg['storage'].get('filler_threshold') or 0, 'filler_threshold')
 
     _text.add_text = cfg['text']['add_text']
     _text.address = cfg['text']['bind_address']
     _text.fontfile = cfg['text']['fontfile']
 
+    _dummy.video = dummy.get('video', 'color')
+    _dummy.color = dummy.get('color', '#121212')
+    _dummy.image = dummy.get('image', '')
+    _dummy.clock = dummy.get('clock', False)
+    _dummy.audio = dummy.get('audio', 'noise')
+
+    _webhook.urls = (cfg.get('webhooks') or {}).get('urls')
+
+    _accept.min_width = to_float(accept.get('min_width') or 0, 'min_width')
+    _accept.min_height = to_float(accept.get('min_height') or 0,
+                                  'min_height')
+    _accept.min_duration = to_float(accept.get('min_duration') or 0,
+                                    'min_duration')
+    _accept.video_codecs = accept.get('video_codecs')
+    _accept.audio_codecs = accept.get('audio_codecs')
+
+    _log.profiling = cfg['logging'].get('profiling', False)
+    _log.level = cfg['logging']['log_level']
+    _log.ff_level = cfg['logging']['ffmpeg_level']
+
     if _init.load:
         _log.to_file = cfg['logging']['log_to_file']
         _log.path = cfg['logging']['log_path']
-        _log.level = cfg['logging']['log_level']
-        _log.ff_level = cfg['logging']['ffmpeg_level']
+        _general.state_path = cfg['general'].get('state_path')
+
+        if not _general.state_path:
+            # without log folder, like when logging to console
+            if _log.path and os.path.isdir(str(_log.path)):
+                _general.state_path = _log.path
+            else:
+                _general.state_path = tempfile.gettempdir()
+        _log.backup_count = cfg['logging'].get('backup_count', 5)
+
+        try:
+            _log.backup_count = int(_log.backup_count)
+
+            if _log.backup_count < 0:
+                raise ValueError
+        except (TypeError, ValueError):
+            _init.errors.append(
+                'backup_count must be a whole number: {}'.format(
+                    _log.backup_count))
+            _log.backup_count = 5
+        _log.compress = cfg['logging'].get('compress_backup', False)
 
         _pre_comp.w = cfg['pre_compress']['width']
         _pre_comp.h = cfg['pre_compress']['height']
-        _pre_comp.aspect = cfg['pre_compress']['aspect']
+        _pre_comp.aspect = to_float(cfg['pre_compress']['aspect'], 'aspect')
         _pre_comp.fps = cfg['pre_compress']['fps']
         _pre_comp.v_bitrate = cfg['pre_compress']['width'] * 50
         _pre_comp.v_bufsize = cfg['pre_compress']['width'] * 50 / 2
 
         _playout.preview = cfg['out']['preview']
-        _playout.name = cfg['out']['service_name']
-        _playout.provider = cfg['out']['service_provider']
-        _playout.post_comp_param = dict_to_list(
-            cfg['out']['post_ffmpeg_param'])
-        _playout.out_addr = cfg['out']['out_addr']
+        _playout.name = expand_vars(cfg['out']['service_name'] or '')
+        _playout.provider = expand_vars(cfg['out']['service_provider'] or '')
+        post_param = dict(cfg['out']['post_ffmpeg_param'])
+        _playout.encoders = post_param.get('c:v')
+
+        if isinstance(_playout.encoders, list):
+            post_param['c:v'] = _playout.encoders[0]
+        else:
+            _playout.encoders = [_playout.encoders]
+
+        _playout.post_comp_param = dict_to_list(post_param)
+        _playout.encoder = post_param.get('c:v')
+        _playout.out_addr = expand_vars(cfg['out']['out_addr'])
+        _playout.backup_addr = expand_vars(cfg['out'].get('backup_addr'))
+
+        _playout.post_process = cfg['out'].get('post_process')
+        _playout.extra_outputs = [
+            (dict_to_list(output.get('post_ffmpeg_param') or {}),
+             expand_vars(output.get('out_addr')))
+            for output in cfg['out'].get('extra_outputs') or []]
+
+        control = cfg.get('control') or {}
+        _control.enable = control.get('enable', False)
+        _control.address = control.get('bind_address', '127.0.0.1:8787')
+        _control.token = expand_vars(control.get('token'))
 
         _init.load = False
 
+    elif _init.errors:
+        # on reload the running config stays active
+        raise ValueError('\n'.join(_init.errors))
+
 
 load_config()
 
@@ -291,16 +579,60 @@ class CustomFormatter(logging.Formatter):
         return formatter.format(record)
 
 
+def gzip_rotator(source, dest):
+    """
+    compress rotated log file
+    """
+    with open(source, 'rb') as f_in, gzip.open(dest, 'wb') as f_out:
+        shutil.copyfileobj(f_in, f_out)
+
+    os.remove(source)
+
+
+def file_handler(log_file):
+    """
+    rotate log file every midnight and keep backup_count old files,
+    compress them when it is set in config
+    """
+    handler = TimedRotatingFileHandler(log_file, when='midnight',
+                                       backupCount=_log.backup_count)
+
+    if _log.compress:
+        handler.namer = lambda name: name + '.gz'
+        handler.rotator = gzip_rotator
+
+    return handler
+
+
 # If the log file is specified on the command line then override the default
 if stdin_args.log:
     _log.path = stdin_args.log
 
+LOG_LEVELS = ['DEBUG', 'INFO', 'WARNING', 'ERROR']
+
 playout_logger = logging.getLogger('playout')
-playout_logger.setLevel(_log.level)
 decoder_logger = logging.getLogger('decoder')
-decoder_logger.setLevel(_log.ff_level)
 encoder_logger = logging.getLogger('encoder')
-encoder_logger.setLevel(_log.ff_level)
+
+
+def set_log_levels():
+    """
+    set logger levels from config, at start and on reload
+    """
+    playout_logger.setLevel(_log.level)
+    decoder_logger.setLevel(_log.ff_level)
+    encoder_logger.setLevel(_log.ff_level)
+
+
+def ff_log_level(logger):
+    """
+    ffmpeg log level from the current logger level,
+    so a changed level is used by the next decoder
+    """
+    return logging.getLevelName(logger.level).lower()
+
+
+set_log_levels()
 
 if _log.to_file and _log.path != 'none':
     if _log.path and os.path.isdir(_log.path):
@@ -317,12 +649,9 @@ if _log.to_file and _log.path != 'none':
 
     p_format = logging.Formatter('[%(asctime)s] [%(levelname)s]  %(message)s')
     f_format = logging.Formatter('[%(asctime)s]  %(message)s')
-    p_file_handler = TimedRotatingFileHandler(playout_log, when='midnight',
-                                              backupCount=5)
-    d_file_handler = TimedRotatingFileHandler(decoder_log, when='midnight',
-                                              backupCount=5)
-    e_file_handler = TimedRotatingFileHandler(encoder_log, when='midnight',
-                                              backupCount=5)
+    p_file_handler = file_handler(playout_log)
+    d_file_handler = file_handler(decoder_log)
+    e_file_handler = file_handler(encoder_log)
 
     p_file_handler.setFormatter(p_format)
     d_file_handler.setFormatter(f_format)
@@ -448,10 +777,87 @@ class Messenger:
         playout_logger.error(msg.replace('\n', ' '))
         self._mailer.error(msg)
 
+    def report(self, msg):
+        playout_logger.info(msg.replace('\n', ' '))
+
+        if _mail.report:
+            # sent on day change from the playout loop, so don't wait for it
+            thread = Thread(name='report', target=self._mailer.send_mail,
+                            args=(msg,))
+            thread.daemon = True
+            thread.start()
+
 
 messenger = Messenger()
 
 
+class StageTimer:
+    """
+    measure time of the processing stages from every clip,
+    with this we can find out why clip transitions are slow
+    """
+
+    def __init__(self):
+        self.stages = []
+        self.last = time.perf_counter()
+
+    def reset(self):
+        self.stages = []
+        self.last = time.perf_counter()
+
+    def mark(self, stage):
+        now = time.perf_counter()
+        self.stages.append('{}: {:.3f}s'.format(stage, now - self.last))
+        self.last = now
+
+    def report(self, src):
+        if _log.profiling:
+            messenger.info('Profiling "{}": {}'.format(
+                src, ', '.join(self.stages)))
+
+        self.reset()
+
+
+stage_timer = StageTimer()
+
+
+# ------------------------------------------------------------------------------
+# webhooks
+# ------------------------------------------------------------------------------
+
+def webhook(event, **data):
+    """
+    post playout event as json to all webhook urls from config,
+    sending runs in background, so it don't block the playout
+    """
+    daily_report.add(event)
+
+    if not _webhook.urls:
+        return
+
+    data.update({
+        'event': event,
+        'time': datetime.now().isoformat(),
+        'channel': _playout.name
+    })
+    body = json.dumps(data).encode('utf-8')
+
+    def post(url):
+        req = request.Request(url, data=body, method='POST', headers={
+            'Content-Type': 'application/json'})
+
+        try:
+            request.urlopen(req, timeout=5).close()
+        except (OSError, ValueError) as err:
+            playout_logger.error(
+                'Webhook "{}" to "{}" failed: {}'.format(event, url, err))
+
+    for url in _webhook.urls:
+        thread = Thread(name='webhook', target=post, args=(url,))
+        thread.daemon = True
+        thread.start()
+
+
 # ------------------------------------------------------------------------------
 # check ffmpeg libs
 # ------------------------------------------------------------------------------
@@ -486,6 +892,237 @@ def ffmpeg_libs():
 FF_LIBS = ffmpeg_libs()
 
 
+def select_encoder():
+    """
+    c:v in post_ffmpeg_param can be a list of encoders,
+    use the first one which ffmpeg can initialize,
+    so a driver problem with hardware encoders don't stop the playout,
+    return error message when no encoder works
+    """
+    params = _playout.post_comp_param
+
+    if len(_playout.encoders) < 2 or '-c:v' not in params or \
+            _playout.preview or stdin_args.desktop:
+        return
+
+    for encoder in _playout.encoders:
+        cmd = ['ffmpeg', '-v', 'error', '-hide_banner', '-f', 'lavfi',
+               '-i', 'color=s=256x144:d=0.2', '-c:v', encoder,
+               '-f', 'null', '-']
+
+        try:
+            check_output(cmd, stderr=STDOUT)
+        except CalledProcessError as err:
+            messenger.warning('Encoder "{}" not usable:\n{}'.format(
+                encoder, err.output.decode('UTF-8').strip()))
+            continue
+
+        params[params.index('-c:v') + 1] = encoder
+        _playout.encoder = encoder
+        messenger.info('Use video encoder: "{}"'.format(encoder))
+        return
+
+    return 'no video encoder from list is usable: {}'.format(
+        ', '.join(_playout.encoders))
+
+
+def ffmpeg_features(kind):
+    """
+    get names of filters, encoders or muxers, which ffmpeg supports
+    """
+    names = set()
+
+    try:
+        info = check_output(
+            ['ffmpeg', '-hide_banner', '-{}'.format(kind)]).decode('UTF-8')
+    except CalledProcessError:
+        return names
+
+    for line in info.split('\n'):
+        parts = line.split()
+
+        if len(parts) > 1:
+            names.update(parts[1].split(','))
+
+    return names
+
+
+def check_ffmpeg_features():
+    """
+    check that ffmpeg has all filters, encoders and muxers,
+    which are needed for the current config,
+    otherwise ffmpeg would fail later with cryptic errors,
+    return list of missing features
+    """
+    required = {
+        'filters': ['anull', 'apad', 'aresample', 'fade', 'afade', 'fps',
+                    'null', 'pad', 'scale', 'setdar', 'tpad', 'yadif',
+                    'color', 'anoisesrc', 'aevalsrc'],
+        'encoders': ['mpeg2video'],
+        'muxers': ['mpegts']
+    }
+
+    if _pre_comp.add_logo:
+        required['filters'] += ['movie', 'loop', 'format',
+                                'colorchannelmixer', 'overlay']
+
+    if _pre_comp.add_loudnorm:
+        required['filters'].append('loudnorm')
+        required['encoders'].append(
+            'libtwolame' if 'libtwolame' in FF_LIBS else 'mp2')
+    else:
+        required['encoders'].append('s302m')
+
+    if _text.add_text:
+        required['filters'] += ['zmq', 'drawtext']
+
+    if _dummy.video in ['smptebars', 'smptehdbars', 'testsrc2']:
+        required['filters'].append(_dummy.video)
+    elif _dummy.video == 'image':
+        required['filters'] += ['movie', 'loop', 'overlay']
+
+    if _dummy.clock:
+        required['filters'].append('drawtext')
+
+    if _pre_comp.audio_only_video == 'waves':
+        required['filters'].append('showwaves')
+    elif _pre_comp.audio_only_video == 'spectrum':
+        required['filters'].append('showspectrum')
+
+    if _dummy.audio == 'tone':
+        required['filters'].append('sine')
+
+    if _general.tempo:
+        required['filters'] += ['atempo', 'setpts']
+
+    if _playlist.mode and not stdin_args.folder:
+        # gain field from playlist
+        required['filters'].append('volume')
+
+    for comp_filter in (_pre_comp.compressor or {}).values():
+        required['filters'] += [
+            part.split('=')[0].strip() for part in str(comp_filter).split(',')
+            if part.strip()]
+
+    if _text.add_text and _playout.extra_outputs:
+        required['filters'].append('split')
+
+    targets = []
+
+    if not _playout.preview and not stdin_args.desktop:
+        # with post_process the main output goes with its muxer to stdout
+        outputs = [_playout.post_comp_param]
+        outputs += [params for params, _ in _playout.extra_outputs]
+
+        for params in outputs:
+            for key in ['-c:v', '-c:a', '-codec:v', '-codec:a',
+                        '-vcodec', '-acodec']:
+                if key in params and params[params.index(key) + 1] != 'copy':
+                    required['encoders'].append(
+                        params[params.index(key) + 1])
+
+            if '-f' in params:
+                required['muxers'].append(params[params.index('-f') + 1])
+
+        if _playout.extra_outputs or (
+                _playout.backup_addr and not _playout.post_process):
+            required['muxers'].append('tee')
+
+        if not _playout.post_process:
+            targets += [_playout.out_addr, _playout.backup_addr]
+
+        targets += [addr for _, addr in _playout.extra_outputs]
+
+    missing = []
+
+    if 'libsrt' not in FF_LIBS and \
+            [t for t in targets if t and t.startswith('srt://')]:
+        missing.append('ffmpeg is missing protocols: srt (libsrt)')
+
+    for kind, names in required.items():
+        supported = ffmpeg_features(kind)
+
+        if not supported:
+            messenger.warning('Can not read {} from ffmpeg'.format(kind))
+            continue
+
+        not_found = [n for n in names if n not in supported]
+
+        if not_found:
+            missing.append('ffmpeg is missing {}: {}'.format(
+                kind, ', '.join(not_found)))
+
+    return missing
+
+
+def startup_check():
+    """
+    collect config, path and ffmpeg problems before any process starts,
+    and report them together, errors stop the program
+    """
+    errors = list(_init.errors)
+    warnings = []
+
+    encoder_error = select_encoder()
+
+    if encoder_error:
+        errors.append(encoder_error)
+
+    errors += check_ffmpeg_features()
+
+    if not _playout.preview and not stdin_args.desktop and \
+            not _playout.post_process and not _playout.out_addr:
+        errors.append('out_addr is not set')
+
+    if _playout.extra_outputs and \
+            None in [addr for _, addr in _playout.extra_outputs]:
+        errors.append('extra_outputs need an out_addr')
+
+    if stdin_args.folder or not _playlist.mode:
+        path = stdin_args.folder or _storage.path
+        if not os.path.isdir(path):
+            errors.append('storage path not exist: {}'.format(path))
+    elif not stdin_args.playlist and '://' not in _playlist.path and \
+            not os.path.isdir(_playlist.path):
+        warnings.append('playlist path not exist: {}'.format(_playlist.path))
+
+    if _storage.filler and not os.path.isfile(_storage.filler):
+        warnings.append('filler clip not exist: {}'.format(_storage.filler))
+
+    logos = [_pre_comp.logo] if _pre_comp.add_logo else []
+    logos += [logo.get('logo') for logo in _pre_comp.extra_logos or []]
+
+    for logo in logos:
+        if not logo or not os.path.isfile(logo):
+            warnings.append('logo not exist: {}'.format(logo))
+
+    if _dummy.video == 'image' and not os.path.isfile(_dummy.image):
+        warnings.append('dummy image not exist: {}'.format(_dummy.image))
+
+    if (_general.quarantine or _storage.shuffle) and \
+            not os.path.isdir(_general.state_path):
+        warnings.append('state path not exist, quarantine and shuffle state '
+                        'are not saved: {}'.format(_general.state_path))
+
+    if _init.unknown:
+        warnings.append('unknown config settings, they are ignored: {}'.format(
+            ', '.join(_init.unknown)))
+
+    _init.warnings = warnings
+
+    if warnings:
+        messenger.warning('Startup check:\n- {}'.format(
+            '\n- '.join(warnings)))
+
+    if errors:
+        messenger.error('Startup check failed, program stops:\n- {}'.format(
+            '\n- '.join(errors)))
+        sys.exit(1)
+
+
+startup_check()
+
+
 # ------------------------------------------------------------------------------
 # probe media infos
 # ------------------------------------------------------------------------------
@@ -499,6 +1136,7 @@ class MediaProbe:
         self.remote_source = ['http', 'https', 'ftp', 'smb', 'sftp']
         self.src = file
         self.format = None
+        self.error = False
         self.audio = []
         self.video = []
 
@@ -521,6 +1159,7 @@ class MediaProbe:
         except CalledProcessError as err:
             messenger.error('MediaProbe error in: "{}"\n {}'.format(self.src,
                                                                     err))
+            self.error = not self.is_remote
             self.audio.append(None)
             self.video.append(None)
 
@@ -532,7 +1171,8 @@ class MediaProbe:
             if stream['codec_type'] == 'audio':
                 self.audio.append(stream)
 
-            if stream['codec_type'] == 'video':
+            if stream['codec_type'] == 'video' and \
+                    not stream.get('disposition', {}).get('attached_pic'):
                 if 'display_aspect_ratio' not in stream:
                     stream['aspect'] = float(
                         stream['width']) / float(stream['height'])
@@ -542,9 +1182,31 @@ class MediaProbe:
 
                 a, b = stream['r_frame_rate'].split('/')
                 stream['fps'] = float(a) / float(b)
+                stream['vfr'] = self.is_vfr(stream)
 
                 self.video.append(stream)
 
+        # audio only source, cover art is not used as video
+        if not self.video:
+            self.video.append(None)
+
+    def is_vfr(self, stream):
+        """
+        when average frame rate differs from real base frame rate,
+        source has a variable frame rate, like phone recordings,
+        interlaced sources can have double field rate, they are ignored
+        """
+        if stream.get('field_order', 'progressive') != 'progressive':
+            return False
+
+        a, b = stream.get('avg_frame_rate', '0/0').split('/')
+
+        if float(a) <= 0 or float(b) <= 0:
+            return False
+
+        return not math.isclose(float(a) / float(b), stream['fps'],
+                                rel_tol=0.01)
+
 
 # ------------------------------------------------------------------------------
 # global helper functions
@@ -560,10 +1222,26 @@ def handle_sigterm(sig, frame):
 def handle_sighub(sig, frame):
     """
     handling SIGHUB signal for reload configuration
-    Linux/macOS only
+    Linux/macOS only,
+    when new config is not valid, roll back to the running config
     """
     messenger.info('Reload config file')
-    load_config()
+    spaces = [_general, _mail, _log, _pre_comp, _playlist, _storage, _text,
+              _dummy, _webhook, _accept]
+    backup = [vars(space).copy() for space in spaces]
+
+    try:
+        load_config()
+        set_log_levels()
+    except (OSError, KeyError, TypeError, ValueError, yaml.YAMLError,
+            SystemExit) as err:
+        for space, values in zip(spaces, backup):
+            vars(space).clear()
+            vars(space).update(values)
+
+        set_log_levels()
+        messenger.error(
+            'Reload config failed, keep running config!\n{}'.format(err))
 
 
 signal.signal(signal.SIGTERM, handle_sigterm)
@@ -582,6 +1260,9 @@ def terminate_processes(watcher=None):
     if _ff.encoder and _ff.encoder.poll() is None:
         _ff.encoder.terminate()
 
+    if _ff.post_process and _ff.post_process.poll() is None:
+        _ff.post_process.terminate()
+
     if watcher:
         watcher.stop()
 
@@ -596,18 +1277,30 @@ def ffmpeg_stderr_reader(std_errors, decoder):
 
     try:
         for line in std_errors:
-            if _log.ff_level == 'INFO':
-                logger.info('{}{}'.format(
-                    prefix, line.decode("utf-8").rstrip()))
-            elif _log.ff_level == 'WARNING':
-                logger.warning('{}{}'.format(
-                    prefix, line.decode("utf-8").rstrip()))
-            else:
-                logger.error('{}{}'.format(
-                    prefix, line.decode("utf-8").rstrip()))
+            # ffmpeg runs with the logger level, see ff_log_level,
+            # so log on the current level, also after a change
+            logger.log(logger.level or logging.ERROR, '{}{}'.format(
+                prefix, line.decode("utf-8").rstrip()))
+    except ValueError:
+        pass
+
+
+def post_process_watcher(process):
+    """
+    log messages from post process and report when it stops
+    """
+    try:
+        for line in process.stderr:
+            encoder_logger.warning('[post process] {}'.format(
+                line.decode("utf-8").rstrip()))
     except ValueError:
         pass
 
+    if process.wait() != 0:
+        messenger.error(
+            'Post process stopped with exit code: {}'.format(
+                process.returncode))
+
 
 def get_date(seek_day):
     """
@@ -645,18 +1338,320 @@ def is_int(value):
         return False
 
 
+def expand_loops(json_object):
+    """
+    repeat clips with 'loop' (play count) or 'repeat' (duration to fill),
+    the last repetition from 'repeat' is cutted to fit the duration
+    """
+    if not json_object or not isinstance(json_object.get('program'), list):
+        return json_object
+
+    program = []
+
+    for node in json_object['program']:
+        loop = node.pop('loop', None)
+        repeat = time_to_sec(node.pop('repeat', None))
+        seek = time_to_sec(node.get('in')) or 0
+        out = time_to_sec(node.get('out'))
+
+        if out is None or out <= seek or (not loop and not repeat):
+            program.append(node)
+            continue
+
+        length = out - seek
+
+        if repeat:
+            count = math.ceil(repeat / length)
+        else:
+            try:
+                count = max(int(loop), 1)
+            except (TypeError, ValueError):
+                messenger.error(
+                    'Loop value "{}" is not a number'.format(loop))
+                count = 1
+
+        for i in range(count):
+            clip = dict(node)
+
+            if repeat and i == count - 1:
+                clip['out'] = seek + repeat - length * i
+
+            program.append(clip)
+
+    json_object['program'] = program
+
+    return json_object
+
+
+def stretch_clip(node, length):
+    """
+    play a clip longer, first more from its end, then from its begin,
+    but never over the source duration, return the time which not fits
+    """
+    seek = time_to_sec(node.get('in')) or 0
+    out = time_to_sec(node.get('out'))
+    duration = time_to_sec(node.get('duration'))
+
+    if out is None or duration is None:
+        return length
+
+    end = min(length, max(duration - out, 0.0))
+    begin = min(length - end, seek)
+    node['in'] = seek - begin
+    node['out'] = out + end
+
+    return length - end - begin
+
+
+def handle_short_clips(json_object):
+    """
+    clips shorter then min_clip_length cause fast ffmpeg restarts,
+    depending on short_clip they are skipped, padded to minimum length,
+    or there length gets added to the clip before (or after, when first),
+    what not fits in the source goes to the next clip
+    """
+    if not json_object or not _playlist.min_length or \
+            not isinstance(json_object.get('program'), list):
+        return json_object
+
+    program = []
+    carry = 0.0
+
+    for node in json_object['program']:
+        seek = time_to_sec(node.get('in')) or 0
+        out = time_to_sec(node.get('out'))
+        length = out - seek if out is not None else None
+
+        if length is None or length >= _playlist.min_length:
+            if carry:
+                carry = stretch_clip(node, carry)
+
+            program.append(node)
+            continue
+
+        messenger.warning(
+            'Clip is shorter then {} seconds, {}: {}'.format(
+                _playlist.min_length, _playlist.short_clip,
+                node.get('source')))
+        length = max(length, 0.0)
+
+        if _playlist.short_clip == 'pad':
+            node['out'] = seek + _playlist.min_length
+            program.append(node)
+        elif _playlist.short_clip == 'merge':
+            if program:
+                carry += stretch_clip(program[-1], length)
+            else:
+                carry += length
+
+    if carry > 0.01:
+        messenger.warning(
+            'Short clips not fit in the clips around, playlist is {:.2f} '
+            'seconds shorter'.format(carry))
+
+    json_object['program'] = program
+
+    return json_object
+
+
 def valid_json(file):
     """
     simple json validation
     """
     try:
         json_object = json.load(file)
-        return json_object
+        return handle_short_clips(expand_loops(json_object))
     except ValueError:
         messenger.error("Playlist {} is not JSON conform".format(file))
         return None
 
 
+# durations by source and modification time, so a playlist without
+# durations is only probed once, and not again on every change
+DURATIONS = {}
+
+
+def probe_duration(src):
+    """
+    get duration from media file, for playlists without durations
+    """
+    try:
+        key = (src, os.path.getmtime(src))
+    except OSError:
+        key = (src, None)
+
+    if key not in DURATIONS:
+        probe = MediaProbe()
+        probe.load(src)
+
+        if probe.format and 'duration' in probe.format:
+            DURATIONS[key] = float(probe.format['duration'])
+        else:
+            return None
+
+    return DURATIONS[key]
+
+
+def m3u_to_json(file, base_dir):
+    """
+    convert m3u/m3u8 playlist to json program,
+    duration comes from #EXTINF, or from the file when it is missing,
+    relative paths are relative to the playlist folder
+    """
+    program = []
+    duration = None
+
+    for line in file:
+        line = line.strip()
+
+        if line.startswith('#EXTINF:'):
+            duration = time_to_sec(line[8:].split(',')[0])
+        elif line and not line.startswith('#'):
+            if '://' not in line and not os.path.isabs(line):
+                line = os.path.join(base_dir, line)
+
+            if not duration or duration <= 0:
+                duration = probe_duration(line)
+
+            program.append({'in': 0, 'out': duration, 'duration': duration,
+                            'source': line})
+            duration = None
+
+    return {'program': program}
+
+
+def xspf_to_json(file, base_dir):
+    """
+    convert xspf playlist to json program, duration is in milliseconds,
+    or from the file when it is missing,
+    locations are file URIs or paths relative to the playlist folder
+    """
+    program = []
+    tree = ET.parse(file)
+
+    for track in tree.getroot().iter('{http://xspf.org/ns/0/}track'):
+        location = track.findtext('{http://xspf.org/ns/0/}location')
+        duration = track.findtext('{http://xspf.org/ns/0/}duration')
+
+        if not location or not location.strip():
+            continue
+
+        location = location.strip()
+
+        if location.startswith('file://'):
+            location = unquote(urlparse(location).path)
+        elif '://' not in location and not os.path.isabs(location):
+            location = os.path.join(base_dir, unquote(location))
+
+        try:
+            duration = float(duration) / 1000
+        except (TypeError, ValueError):
+            duration = probe_duration(location)
+
+        program.append({'in': 0, 'out': duration, 'duration': duration,
+                        'source': location})
+
+    return {'program': program}
+
+
+def csv_to_json(file):
+    """
+    convert csv schedule with header line to json program,
+    csv_columns maps playlist keys to the column names,
+    without out and duration, the duration comes from the file
+    """
+    columns = _playlist.csv_columns or {}
+    sample = file.read(4096)
+    file.seek(0)
+
+    try:
+        dialect = csv.Sniffer().sniff(sample, delimiters=',;\t')
+    except csv.Error:
+        dialect = csv.excel
+
+    program = []
+
+    for row in csv.DictReader(file, dialect=dialect):
+        node = {}
+
+        for key in ['source', 'in', 'out', 'duration', 'category',
+                    'subtitle', 'gain']:
+            value = row.get(columns.get(key, key))
+
+            if value is not None and value.strip():
+                node[key] = value.strip()
+
+        if 'source' not in node:
+            continue
+
+        if 'out' not in node and 'duration' not in node:
+            node['duration'] = probe_duration(node['source'])
+
+        node.setdefault('in', 0)
+        node.setdefault('category', '')
+        node.setdefault('out', node.get('duration'))
+        node.setdefault('duration', node['out'])
+        program.append(node)
+
+    return {'program': program}
+
+
+def playlist_path(list_date):
+    """
+    path to playlist from date, first existing from json, m3u8, m3u, xspf
+    and csv, without any the json path is returned
+    """
+    year, month, day = list_date.split('-')
+    base = os.path.join(_playlist.path, year, month, list_date)
+
+    if '://' not in base:
+        for ext in ['.json', '.m3u8', '.m3u', '.xspf', '.csv']:
+            if os.path.isfile(base + ext):
+                return base + ext
+
+    return base + '.json'
+
+
+def read_playlist(path):
+    """
+    read playlist file, by extension as json, m3u/m3u8, xspf or csv
+    """
+    ext = os.path.splitext(path)[1].lower()
+
+    with open(path, 'r', encoding='utf-8-sig') as f:
+        if ext in ['.m3u', '.m3u8']:
+            return handle_short_clips(expand_loops(
+                m3u_to_json(f, os.path.dirname(path))))
+        elif ext == '.xspf':
+            try:
+                return handle_short_clips(expand_loops(
+                    xspf_to_json(f, os.path.dirname(path))))
+            except ET.ParseError as err:
+                messenger.error('Playlist is not valid xspf: {}'.format(err))
+                return None
+        elif ext == '.csv':
+            return handle_short_clips(expand_loops(csv_to_json(f)))
+
+        return valid_json(f)
+
+
+def validate_playlist_file(path):
+    """
+    read and validate playlist file in background,
+    m3u, xspf and csv files can need probing for durations,
+    that also fills the durations for the day change
+    """
+    def read_and_check():
+        clip_nodes = read_playlist(path)
+
+        if clip_nodes:
+            validate_thread(clip_nodes)
+
+    check = Thread(name='check_file', target=read_and_check)
+    check.daemon = True
+    check.start()
+
+
 def check_sync(delta):
     """
     check that we are in tolerance time
@@ -679,11 +1674,68 @@ def check_length(total_play_time):
             'Playlist ({}) is not long enough!\n'
             'Total play time is: {}, target length is: {}'.format(
                 get_date(True),
-                timedelta(seconds=total_play_time),
-                timedelta(seconds=_playlist.length))
+                sec_to_time(total_play_time),
+                sec_to_time(_playlist.length))
         )
 
 
+def check_acceptance(probe):
+    """
+    check media against technical acceptance rules from config,
+    return list with rule violations
+    """
+    violations = []
+    video = probe.video[0] if probe.video else None
+
+    if video:
+        if _accept.min_width and int(video['width']) < _accept.min_width \
+                or _accept.min_height and \
+                int(video['height']) < _accept.min_height:
+            violations.append('Resolution {}x{} is too small'.format(
+                video['width'], video['height']))
+
+        if _accept.video_codecs and \
+                video['codec_name'] not in _accept.video_codecs:
+            violations.append('Video codec "{}" is not allowed'.format(
+                video['codec_name']))
+
+    for audio in probe.audio:
+        if audio and _accept.audio_codecs and \
+                audio['codec_name'] not in _accept.audio_codecs:
+            violations.append('Audio codec "{}" is not allowed'.format(
+                audio['codec_name']))
+
+    if _accept.min_duration and probe.format and \
+            float(probe.format.get('duration', 0)) < _accept.min_duration:
+        violations.append('Duration {} is too short'.format(
+            sec_to_time(float(probe.format.get('duration', 0)))))
+
+    return violations
+
+
+def wait_for_load():
+    """
+    pause background validation while the host is busy,
+    so it don't compete with the encoder,
+    load average per cpu core is compared with validation_load
+    """
+    if not _general.max_load or not hasattr(os, 'getloadavg'):
+        return
+
+    cores = os.cpu_count() or 1
+    paused = False
+
+    while os.getloadavg()[0] / cores > _general.max_load:
+        if not paused:
+            messenger.debug('System load is high, pause validation')
+            paused = True
+
+        time.sleep(10)
+
+    if paused:
+        messenger.debug('Resume validation')
+
+
 def validate_thread(clip_nodes):
     """
     validate json values in new thread
@@ -696,22 +1748,36 @@ def validate_thread(clip_nodes):
 
         # check if all values are valid
         for node in json_nodes["program"]:
+            wait_for_load()
             source = node["source"]
             probe.load(source)
             missing = []
 
             if probe.is_remote:
-                if not probe.video[0]:
+                if not probe.video[0] and not (
+                        probe.audio and probe.audio[0]):
                     missing.append('Stream not exist: "{}"'.format(source))
             elif not os.path.isfile(source):
                 missing.append('File not exist: "{}"'.format(source))
+            elif quarantine.is_blocked(source):
+                missing.append('File is in quarantine: "{}"'.format(source))
+            elif probe.video and probe.video[0] and probe.video[0]['vfr']:
+                messenger.warning(
+                    'Variable frame rate in: "{}", '
+                    'fps will be forced to {}'.format(source, _pre_comp.fps))
+
+            if probe.format:
+                missing += check_acceptance(probe)
 
-            if is_float(node["in"]) and is_float(node["out"]):
-                counter += node["out"] - node["in"]
+            seek = time_to_sec(node["in"])
+            out = time_to_sec(node["out"])
+
+            if seek is not None and out is not None:
+                counter += out - seek
             else:
                 missing.append('Missing Value in: "{}"'.format(node))
 
-            if not is_float(node["duration"]):
+            if time_to_sec(node["duration"]) is None:
                 missing.append('No duration Value!')
 
             line = '\n'.join(missing)
@@ -763,25 +1829,225 @@ def loop_input(source, src_duration, target_duration):
 
 def gen_dummy(duration):
     """
-    generate a dummy clip, with black color and empty audiotrack
+    generate a dummy clip, for missing sources,
+    video can be a color, test pattern or image, optional with clock,
+    audio can be noise, silence or a test tone
     """
-    color = '#121212'
-    # IDEA: add noise could be an config option
-    # noise = 'noise=alls=50:allf=t+u,hue=s=0'
-    return [
-        '-f', 'lavfi', '-i',
-        'color=c={}:s={}x{}:d={}:r={},format=pix_fmts=yuv420p'.format(
-            color, _pre_comp.w, _pre_comp.h, duration, _pre_comp.fps
-        ),
-        '-f', 'lavfi', '-i', 'anoisesrc=d={}:c=pink:r=48000:a=0.05'.format(
+    size = '{}x{}'.format(_pre_comp.w, _pre_comp.h)
+    video = 'color=c={}:s={}:d={}:r={}'.format(
+        _dummy.color, size, duration, _pre_comp.fps)
+
+    if _dummy.video in ['smptebars', 'smptehdbars', 'testsrc2']:
+        video = '{}=s={}:d={}:r={}'.format(
+            _dummy.video, size, duration, _pre_comp.fps)
+    elif _dummy.video == 'image' and _dummy.image and \
+            os.path.isfile(_dummy.image):
+        video += '[bg];movie={},loop=loop=-1:size=1:start=0,scale={}:{}:' \
+            'force_original_aspect_ratio=decrease[img];' \
+            '[bg][img]overlay=(W-w)/2:(H-h)/2:shortest=1'.format(
+                _dummy.image, _pre_comp.w, _pre_comp.h)
+
+    if _dummy.clock and os.path.isfile(_text.fontfile):
+        video += ",drawtext=fontfile='{}':text='%{{localtime\\:%X}}':" \
+            "fontsize=h/12:fontcolor=white:box=1:boxcolor=black@0.5:" \
+            "x=(w-tw)/2:y=h-th-h/12".format(_text.fontfile)
+
+    if _dummy.audio == 'silence':
+        audio = 'aevalsrc=0:channel_layout=2:duration={}:' \
+            'sample_rate=48000'.format(duration)
+    elif _dummy.audio == 'tone':
+        audio = 'sine=frequency=1000:sample_rate=48000:duration={}'.format(
             duration)
+    else:
+        audio = 'anoisesrc=d={}:c=pink:r=48000:a=0.05'.format(duration)
+
+    return [
+        '-f', 'lavfi', '-i', video + ',format=pix_fmts=yuv420p',
+        '-f', 'lavfi', '-i', audio
     ]
 
 
+class FillerCounter:
+    """
+    sum up how long filler was played on the current playlist day,
+    when it is more then filler_threshold, playlists are too short
+    or clips are missing frequently
+    """
+
+    def __init__(self):
+        self.date = None
+        self.total = 0.0
+        self.warned = False
+        self.last = (None, 0.0)
+
+    def add(self, duration):
+        list_date = get_date(True)
+
+        if list_date != self.date:
+            self.last = (self.date, self.total)
+            self.date = list_date
+            self.total = 0.0
+            self.warned = False
+
+        self.total += duration
+        messenger.debug(
+            'Filler time for {0}: {1:.2f} seconds'.format(
+                self.date, self.total))
+
+        if _storage.filler_threshold and not self.warned \
+                and self.total > _storage.filler_threshold:
+            self.warned = True
+            messenger.warning(
+                'Filler time for {} is over threshold!\n'
+                'Played filler: {}, threshold is: {}'.format(
+                    self.date, sec_to_time(self.total),
+                    sec_to_time(_storage.filler_threshold)))
+
+
+filler_counter = FillerCounter()
+
+
+class DailyReport:
+    """
+    count incidents of the playlist day, on day change log a summary
+    with a health score: 100 minus weight per incident
+    and one point per minute filler
+    """
+    weights = {
+        'encoder_error': 10,
+        'playlist_missing': 10,
+        'clip_error': 5,
+        'clip_missing': 2,
+        'drift_correction': 2
+    }
+
+    def __init__(self):
+        self.date = None
+        self.incidents = {}
+
+    def add(self, event):
+        list_date = get_date(True)
+
+        if list_date != self.date:
+            last_date, incidents = self.date, self.incidents
+            self.date, self.incidents = list_date, {}
+
+            if last_date:
+                self.send(last_date, incidents)
+
+        if event in self.weights:
+            self.incidents[event] = self.incidents.get(event, 0) + 1
+
+    def send(self, date, incidents):
+        if filler_counter.date == date:
+            filler = filler_counter.total
+        elif filler_counter.last[0] == date:
+            filler = filler_counter.last[1]
+        else:
+            filler = 0.0
+
+        score = max(0, 100 - int(filler / 60) - sum(
+            weight * incidents.get(event, 0)
+            for event, weight in self.weights.items()))
+
+        messenger.report(
+            'Daily report for {}, health score: {}\n{}\nfiller: {}'.format(
+                date, score, '\n'.join(
+                    '{}: {}'.format(event, incidents.get(event, 0))
+                    for event in self.weights), sec_to_time(filler)))
+        webhook('daily_report', date=date, score=score,
+                incidents=incidents, filler=round(filler, 3))
+
+
+daily_report = DailyReport()
+
+
+class Quarantine:
+    """
+    count playout failures per clip, after quarantine_threshold failures
+    in a row, the clip gets skipped until it is removed from the
+    quarantine file, so a corrupt file can't break every schedule,
+    playout and validation thread use it, so changes are locked
+    """
+
+    def __init__(self):
+        self.file = os.path.join(_general.state_path,
+                                 'ffplayout_quarantine.json')
+        self.lock = Lock()
+
+    def load(self):
+        if not os.path.isfile(self.file):
+            return {}
+
+        try:
+            with open(self.file, 'r', encoding='utf-8') as f:
+                return json.load(f)
+        except (OSError, ValueError):
+            return {}
+
+    def save(self, failures):
+        try:
+            with open(self.file, 'w', encoding='utf-8') as f:
+                json.dump(failures, f, indent=4)
+        except OSError as err:
+            messenger.error('Saving quarantine failed: {}'.format(err))
+
+    def is_blocked(self, src):
+        if not _general.quarantine:
+            return False
+
+        with self.lock:
+            return self.load().get(src, 0) >= _general.quarantine
+
+    def blocked(self):
+        """
+        all clips in quarantine, to check many clips with one read
+        """
+        if not _general.quarantine:
+            return set()
+
+        with self.lock:
+            return {src for src, count in self.load().items()
+                    if count >= _general.quarantine}
+
+    def failed(self, src):
+        if not _general.quarantine:
+            webhook('clip_error', source=src)
+            return
+
+        with self.lock:
+            failures = self.load()
+            failures[src] = failures.get(src, 0) + 1
+            self.save(failures)
+
+        webhook('clip_error', source=src, failures=failures[src])
+
+        if failures[src] >= _general.quarantine:
+            messenger.error(
+                'Clip failed {} times, put it in quarantine:\n"{}"\n'
+                'Remove it from "{}" to play it again'.format(
+                    failures[src], src, self.file))
+
+    def passed(self, src):
+        if not _general.quarantine:
+            return
+
+        with self.lock:
+            failures = self.load()
+
+            if src in failures and failures[src] < _general.quarantine:
+                del failures[src]
+                self.save(failures)
+
+
+quarantine = Quarantine()
+
+
 def gen_filler(duration):
     """
     when playlist is not 24 hours long, we generate a loop from filler clip
     """
+    filler_counter.add(duration)
     probe = MediaProbe()
     probe.load(_storage.filler)
 
@@ -815,11 +2081,15 @@ def src_or_dummy(probe, src, dur, seek, out):
     """
 
     # check if input is a remote source
-    if probe.is_remote and probe.video[0]:
+    if probe.is_remote and (probe.video[0] or
+                            probe.audio and probe.audio[0]):
         if seek > 0.0:
             messenger.warning(
                 'Seek in live source "{}" not supported!'.format(src))
         return ['-i', src] + set_length(86400.0, seek, out)
+    elif src and quarantine.is_blocked(src):
+        messenger.error('Clip is in quarantine, skip:\n{}'.format(src))
+        return gen_dummy(out - seek)
     elif src and os.path.isfile(src):
         if out > dur:
             if seek > 0.0:
@@ -834,6 +2104,7 @@ def src_or_dummy(probe, src, dur, seek, out):
             return seek_in(seek) + ['-i', src] + set_length(dur, seek, out)
     else:
         messenger.error('Clip/URL not exist:\n{}'.format(src))
+        webhook('clip_missing', source=src)
         return gen_dummy(out - seek)
 
 
@@ -930,6 +2201,44 @@ def handle_list_end(probe, new_length, src, begin, dur, seek, out):
     return src_cmd, seek, new_out, new_playlist
 
 
+def correct_drift(src, delta, seek, out):
+    """
+    when playout is later then the playlist more then drift_correction,
+    cut the delay from the begin of the current clip,
+    when the rest of the clip is too short, skip it
+    """
+    if out - seek + delta < 1.0:
+        messenger.warning(
+            'Playout is {:.2f} seconds late, skip clip:\n{}'.format(
+                -delta, src))
+        webhook('drift_correction', source=src, delta=delta, action='skip')
+        return None
+
+    messenger.warning(
+        'Playout is {:.2f} seconds late, cut begin of clip:\n{}'.format(
+            -delta, src))
+    webhook('drift_correction', source=src, delta=delta, action='trim')
+
+    return seek - delta
+
+
+def drift_tempo(delta, seek, out):
+    """
+    when playout is late, get the speed factor which catches up the delay
+    in the current clip, it is only used when the change stays
+    under drift_tempo percent, so long clips can absorb the delay smoothly
+    """
+    if not _general.tempo or delta > -1.0 or out - seek <= 0:
+        return 1.0
+
+    factor = -delta / (out - seek)
+
+    if factor > _general.tempo / 100:
+        return 1.0
+
+    return round(1.0 + factor, 6)
+
+
 def timed_source(probe, src, begin, dur, seek, out, first, last):
     """
     prepare input clip
@@ -937,6 +2246,7 @@ def timed_source(probe, src, begin, dur, seek, out, first, last):
     return clip only if we are in 24 hours time range
     """
     current_delta, total_delta = get_delta(begin)
+    _current.tempo = 1.0
 
     if first:
         _seek, _out, new_list = handle_list_init(current_delta, total_delta,
@@ -950,6 +2260,23 @@ def timed_source(probe, src, begin, dur, seek, out, first, last):
 
     else:
         if not stdin_args.loop and _playlist.length:
+            _current.drift = current_delta
+            tempo = drift_tempo(current_delta, seek, out)
+
+            if tempo != 1.0 and not last and total_delta > out - seek:
+                messenger.info(
+                    'Playout is {:.2f} seconds late, play clip with '
+                    'tempo {}:\n{}'.format(-current_delta, tempo, src))
+                _current.tempo = tempo
+                current_delta = 0.0
+            elif _general.drift and -current_delta > _general.drift:
+                seek = correct_drift(src, current_delta, seek, out)
+
+                if seek is None:
+                    return None, 0, 0, False
+
+                current_delta = 0.0
+
             check_sync(current_delta)
             messenger.debug('current_delta: {:f}'.format(current_delta))
             messenger.debug('total_delta: {:f}'.format(total_delta))
@@ -972,6 +2299,75 @@ def timed_source(probe, src, begin, dur, seek, out, first, last):
             return None, 0, 0, True
 
 
+def expand_time_vars(addr):
+    """
+    replace {date} and {hour} in output address with the current time,
+    for local files the folder gets created
+    """
+    now = datetime.now()
+    addr = addr.replace('{date}', now.strftime('%Y-%m-%d')).replace(
+        '{hour}', now.strftime('%H'))
+
+    if '://' not in addr and os.path.dirname(addr):
+        os.makedirs(os.path.dirname(addr), exist_ok=True)
+
+    return addr
+
+
+def tee_target(params, addrs):
+    """
+    send one encoding over the tee muxer to the addresses,
+    a failing address is dropped and does not stop the others
+    """
+    params = list(params)
+    tee_opts = 'onfail=ignore'
+
+    if '-f' in params:
+        index = params.index('-f')
+        tee_opts = 'f={}:{}'.format(params[index + 1], tee_opts)
+        del params[index:index + 2]
+
+    targets = '|'.join(['[{}]{}'.format(tee_opts, addr) for addr in addrs])
+
+    return params + ['-f', 'tee', targets]
+
+
+def output_target(overlay, metadata):
+    """
+    set encoder outputs with parameters and target address,
+    when backup_addr is set, push the same stream to both addresses,
+    extra outputs have there own encoding and are split from the same input,
+    with post_process the main output goes to stdout
+    """
+    outputs = []
+    labels = ['0:v'] * (len(_playout.extra_outputs) + 1)
+
+    if overlay and _playout.extra_outputs:
+        # text overlay must be on all outputs, but zmq can only bind once
+        labels = ['[v{}]'.format(i) for i in range(len(labels))]
+        outputs += ['-filter_complex', '[0:v]{},split={}{}'.format(
+            overlay[1], len(labels), ''.join(labels))]
+        overlay = []
+
+    outputs += overlay + ['-map', labels[0], '-map', '0:a'] + metadata
+
+    if _playout.post_process:
+        outputs += _playout.post_comp_param + ['pipe:1']
+    elif _playout.backup_addr:
+        outputs += tee_target(_playout.post_comp_param, [
+            expand_time_vars(_playout.out_addr),
+            expand_time_vars(_playout.backup_addr)])
+    else:
+        outputs += _playout.post_comp_param + [
+            expand_time_vars(_playout.out_addr)]
+
+    for label, (params, addr) in zip(labels[1:], _playout.extra_outputs):
+        outputs += ['-map', label, '-map', '0:a'] + metadata + tee_target(
+            params, [expand_time_vars(addr)])
+
+    return outputs
+
+
 def pre_audio_codec():
     """
     when add_loudnorm is False we use a different audio encoder,